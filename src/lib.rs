@@ -1,14 +1,102 @@
 use std::env;
 use std::fmt::Display;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
-use std::process;
+use std::process::{self, Stdio};
 
 #[derive(Debug)]
 pub struct Shell {
     builtins: Vec<&'static str>,
     path_dirs: Vec<PathBuf>,
+    last_status: i32,
+    history: Vec<String>,
+    history_limit: Option<usize>,
+    config: Config,
+}
+
+/// Settings loaded from `~/.shellrc` at startup. Missing or unreadable config
+/// files just fall back to [`Config::default`], so the shell always works
+/// without one.
+#[derive(Debug, Clone)]
+struct Config {
+    prompt: String,
+    history_limit: Option<usize>,
+    show_errors: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prompt: "$ ".to_string(),
+            history_limit: None,
+            show_errors: true,
+        }
+    }
+}
+
+impl Config {
+    /// Reads `~/.shellrc` as simple `key: value` lines. Unknown keys and
+    /// malformed values are warned about on stderr and otherwise ignored,
+    /// since a typo in a config file shouldn't stop the shell from starting.
+    fn load() -> Self {
+        let mut config = Config::default();
+        let Some(path) = config_file_path() else {
+            return config;
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return config;
+        };
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                eprintln!("shellrc: ignoring malformed line: {}", line);
+                continue;
+            };
+            let key = key.trim();
+            let value = unquote(value.trim());
+
+            match key {
+                "prompt" => config.prompt = value,
+                "history_limit" => match value.as_str() {
+                    "none" | "unlimited" => config.history_limit = None,
+                    n => match n.parse::<usize>() {
+                        Ok(limit) => config.history_limit = Some(limit),
+                        Err(_) => {
+                            eprintln!("shellrc: invalid history_limit value: {}", value)
+                        }
+                    },
+                },
+                "show_errors" => match value.parse::<bool>() {
+                    Ok(show_errors) => config.show_errors = show_errors,
+                    Err(_) => eprintln!("shellrc: invalid show_errors value: {}", value),
+                },
+                other => eprintln!("shellrc: ignoring unknown key: {}", other),
+            }
+        }
+
+        config
+    }
+}
+
+/// Strips a single pair of surrounding double quotes, so values with
+/// meaningful leading/trailing whitespace (like `prompt: "> "`) survive.
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".shellrc"))
 }
 
 #[derive(Debug)]
@@ -17,6 +105,7 @@ pub enum ShellError {
     ShellMessage(String),
     CommandNotFound(String),
     NotFound(String),
+    PermissionDenied(String),
 }
 
 impl Display for ShellError {
@@ -26,6 +115,7 @@ impl Display for ShellError {
             ShellError::ShellMessage(s) => write!(f, "{}", s),
             ShellError::CommandNotFound(cmd) => write!(f, "{}: command not found", cmd),
             ShellError::NotFound(cmd) => write!(f, "{}: not found", cmd),
+            ShellError::PermissionDenied(cmd) => write!(f, "{}: permission denied", cmd),
         }
     }
 }
@@ -36,103 +126,650 @@ impl From<std::io::Error> for ShellError {
     }
 }
 
+/// Maps a failed command to the exit status POSIX shells would report for it,
+/// so `$?` stays meaningful even when the pipeline as a whole errored out.
+fn exit_code_for(err: &ShellError) -> i32 {
+    match err {
+        ShellError::Io(_) => 1,
+        ShellError::ShellMessage(_) => 2,
+        ShellError::CommandNotFound(_) | ShellError::NotFound(_) => 127,
+        ShellError::PermissionDenied(_) => 126,
+    }
+}
+
+/// The result of resolving a command name against `PATH`, distinguishing "no
+/// such file anywhere on `PATH`" from "found it, but it isn't executable" so
+/// callers can report the right error instead of collapsing both into
+/// "not found".
+enum Lookup {
+    Found(PathBuf),
+    NotExecutable(PathBuf),
+    NotFound,
+}
+
 impl Shell {
     pub fn new() -> Self {
-        let builtins = vec!["exit", "echo", "type", "pwd", "cd"];
+        let builtins = vec![
+            "exit", "echo", "type", "pwd", "cd", "export", "env", "printenv", "history", "which",
+        ];
         let path_dirs = env::var_os("PATH")
             .map(|paths| env::split_paths(&paths).collect())
             .unwrap_or_default();
+        let config = Config::load();
         Self {
             builtins,
             path_dirs,
+            last_status: 0,
+            history: load_history(config.history_limit),
+            history_limit: config.history_limit,
+            config,
         }
     }
 
     pub fn run(mut self) {
+        let mut reader = LineReader::new();
         loop {
-            print!("$ ");
-            io::stdout().flush().unwrap();
-
-            let mut buf = String::new();
-            let _ = io::stdin().read_line(&mut buf).unwrap();
-            let buf = buf.trim_end();
-
-            let cmd = Command::parse(buf);
-            if let Err(err) = cmd.run(&mut self) {
-                match err {
-                    ShellError::CommandNotFound(_) | ShellError::NotFound(_) => println!("{}", err),
-                    other => eprintln!("{}", other),
+            let Some(buf) = read_logical_line(&mut reader, &self.history, &self.config.prompt)
+            else {
+                self.save_history();
+                return;
+            };
+            self.record_history(buf.clone());
+
+            let pipeline = match Pipeline::parse(&buf, &self) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    self.last_status = exit_code_for(&err);
+                    if self.config.show_errors {
+                        eprintln!("{}", err);
+                    }
+                    continue;
+                }
+            };
+            match pipeline.run(&mut self) {
+                Ok(status) => self.last_status = status,
+                Err(err) => {
+                    self.last_status = exit_code_for(&err);
+                    if self.config.show_errors {
+                        match err {
+                            ShellError::CommandNotFound(_)
+                            | ShellError::NotFound(_)
+                            | ShellError::PermissionDenied(_) => {
+                                println!("{}", err)
+                            }
+                            other => eprintln!("{}", other),
+                        }
+                    }
                 }
             }
         }
     }
 
-    fn try_find_executable<'a>(&mut self, name: &'a str) -> Option<PathBuf> {
+    fn try_find_executable(&mut self, name: &str) -> Lookup {
         for dir in &self.path_dirs {
             let candidate_path = dir.join(name);
             #[cfg(unix)]
             if let Ok(metadata) = fs::metadata(&candidate_path) {
-                if metadata.is_file() && is_executable(&metadata) {
-                    return Some(candidate_path);
+                if metadata.is_file() {
+                    if is_executable(&metadata) {
+                        return Lookup::Found(candidate_path);
+                    }
+                    return Lookup::NotExecutable(candidate_path);
                 }
             }
         }
-        None
+        Lookup::NotFound
+    }
+
+    fn record_history(&mut self, line: String) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.history.push(line);
+        if let Some(limit) = self.history_limit {
+            let overflow = self.history.len().saturating_sub(limit);
+            self.history.drain(..overflow);
+        }
+    }
+
+    fn save_history(&self) {
+        let Some(path) = history_file_path() else {
+            return;
+        };
+        let _ = fs::write(path, self.history.join("\n"));
+    }
+}
+
+fn history_file_path() -> Option<PathBuf> {
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".shell_history"))
+}
+
+fn load_history(history_limit: Option<usize>) -> Vec<String> {
+    let mut history: Vec<String> = history_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|contents| contents.lines().map(str::to_string).collect())
+        .unwrap_or_default();
+    if let Some(limit) = history_limit {
+        let overflow = history.len().saturating_sub(limit);
+        history.drain(..overflow);
     }
+    history
 }
 
 trait CommandExec {
-    fn run(self, shell: &mut Shell) -> Result<(), ShellError>;
+    fn run(self, shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError>;
 }
 
-enum Command<'a> {
+enum Command {
     Exit(ExitCmd),
     Echo(EchoCmd),
     Pwd(PwdCmd),
     Cd(CdCmd),
     Type(TypeCmd),
-    External(ExternalCmd<'a>),
+    Export(ExportCmd),
+    Env(EnvCmd),
+    History(HistoryCmd),
+    Which(WhichCmd),
+    External(ExternalCmd),
 }
 
-impl<'a> Command<'a> {
-    fn parse(input: &'a str) -> Self {
-        let (name, args) = input.split_once(' ').unwrap_or((input, ""));
-        let parsed_args = parse_args(args);
-        match name {
-            "exit" => Command::Exit(ExitCmd),
+impl Command {
+    fn from_words(mut words: Vec<String>) -> Self {
+        let name = if words.is_empty() {
+            String::new()
+        } else {
+            words.remove(0)
+        };
+        let args = words;
+        match name.as_str() {
+            "exit" => Command::Exit(ExitCmd { args }),
             "echo" => Command::Echo(EchoCmd {
-                args: parsed_args.join(" "),
+                args: args.join(" "),
             }),
             "type" => Command::Type(TypeCmd {
-                arg: parsed_args.join(" "),
+                arg: args.join(" "),
             }),
             "pwd" => Command::Pwd(PwdCmd),
             "cd" => Command::Cd(CdCmd {
-                path: parsed_args.join(" "),
-            }),
-            other => Command::External(ExternalCmd {
-                name: other,
-                args: parsed_args,
+                path: args.join(" "),
             }),
+            "export" => Command::Export(ExportCmd { args }),
+            "env" | "printenv" => Command::Env(EnvCmd { args }),
+            "history" => Command::History(HistoryCmd),
+            "which" => Command::Which(WhichCmd { args }),
+            _ => Command::External(ExternalCmd { name, args }),
         }
     }
 
-    fn run(self, shell: &mut Shell) -> Result<(), ShellError> {
+    fn run(self, shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
         match self {
-            Command::Exit(exit_cmd) => exit_cmd.run(shell),
-            Command::Echo(echo_cmd) => echo_cmd.run(shell),
-            Command::Pwd(pwd_cmd) => pwd_cmd.run(shell),
-            Command::Cd(cd_cmd) => cd_cmd.run(shell),
-            Command::Type(type_cmd) => type_cmd.run(shell),
-            Command::External(external_cmd) => external_cmd.run(shell),
+            Command::Exit(exit_cmd) => exit_cmd.run(shell, out),
+            Command::Echo(echo_cmd) => echo_cmd.run(shell, out),
+            Command::Pwd(pwd_cmd) => pwd_cmd.run(shell, out),
+            Command::Cd(cd_cmd) => cd_cmd.run(shell, out),
+            Command::Type(type_cmd) => type_cmd.run(shell, out),
+            Command::Export(export_cmd) => export_cmd.run(shell, out),
+            Command::Env(env_cmd) => env_cmd.run(shell, out),
+            Command::History(history_cmd) => history_cmd.run(shell, out),
+            Command::Which(which_cmd) => which_cmd.run(shell, out),
+            Command::External(_) => unreachable!("external commands are spawned by Pipeline::run"),
+        }
+    }
+}
+
+/// A `|`-separated chain of commands, each with its own redirections.
+///
+/// A line with no `|` in it still parses to a single-stage pipeline, so
+/// `Pipeline` is the only entry point `Shell::run` needs.
+struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+struct Stage {
+    words: Vec<String>,
+    redirect: Redirect,
+}
+
+#[derive(Debug, Default)]
+struct Redirect {
+    stdout: Option<RedirectTarget>,
+    stderr: Option<RedirectTarget>,
+    stdin: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct RedirectTarget {
+    path: PathBuf,
+    append: bool,
+}
+
+impl RedirectTarget {
+    fn open(&self) -> io::Result<fs::File> {
+        fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RedirectOp {
+    StdoutTrunc,
+    StdoutAppend,
+    StderrTrunc,
+    StderrAppend,
+    StdinRead,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Word(String),
+    Pipe,
+    Redirect(RedirectOp),
+}
+
+impl Pipeline {
+    fn parse(input: &str, shell: &Shell) -> Result<Self, ShellError> {
+        let tokens = tokenize(input, shell);
+        let stages = split_on_pipes(tokens)
+            .into_iter()
+            .map(|tokens| {
+                let (words, redirect) = parse_stage(tokens)?;
+                Ok(Stage { words, redirect })
+            })
+            .collect::<Result<Vec<_>, ShellError>>()?;
+        Ok(Pipeline { stages })
+    }
+
+    fn run(self, shell: &mut Shell) -> Result<i32, ShellError> {
+        let stage_count = self.stages.len();
+        let mut children = Vec::new();
+        let mut next_stdin: Option<Stdio> = None;
+        let mut status = 0;
+        let mut last_stage_is_external = false;
+        // A non-final builtin's pipe writer must not be drained until the
+        // stage reading from it has actually been spawned, so its write
+        // never blocks on a pipe buffer nobody is reading yet. We hold it
+        // here and flush it right after the next stage exists.
+        let mut pending_builtin: Option<(Command, Box<dyn Write>)> = None;
+
+        for (index, stage) in self.stages.into_iter().enumerate() {
+            let is_last = index + 1 == stage_count;
+            let stdin = match &stage.redirect.stdin {
+                Some(path) => Stdio::from(fs::File::open(path)?),
+                None => next_stdin.take().unwrap_or_else(Stdio::inherit),
+            };
+
+            let command = Command::from_words(stage.words);
+            if is_last {
+                last_stage_is_external = matches!(command, Command::External(_));
+            }
+
+            match command {
+                Command::External(external) => {
+                    let mut command = process::Command::new(&external.name);
+                    command.args(&external.args);
+                    command.stdin(stdin);
+                    command.stdout(resolve_stdout(&stage.redirect, is_last)?);
+                    command.stderr(resolve_stderr(&stage.redirect)?);
+
+                    let mut child = command.spawn().map_err(|_| {
+                        match shell.try_find_executable(&external.name) {
+                            Lookup::NotExecutable(_) => {
+                                ShellError::PermissionDenied(external.name.clone())
+                            }
+                            Lookup::NotFound => {
+                                ShellError::CommandNotFound(external.name.clone())
+                            }
+                            Lookup::Found(_) => ShellError::NotFound(external.name.clone()),
+                        }
+                    })?;
+                    next_stdin = child.stdout.take().map(Stdio::from);
+                    // The child now exists and is reading from the pipe, so
+                    // it's safe to let a builtin upstream of it write.
+                    flush_pending_builtin(&mut pending_builtin, shell, &mut status)?;
+                    children.push(child);
+                }
+                builtin => {
+                    let (sink, reader) = resolve_builtin_sink(&stage.redirect, is_last)?;
+                    next_stdin = reader;
+                    // Nothing downstream for this builtin to wait on, so any
+                    // earlier pending builtin can run now too.
+                    flush_pending_builtin(&mut pending_builtin, shell, &mut status)?;
+                    if is_last {
+                        let mut sink = sink;
+                        let result = builtin.run(shell, sink.as_mut());
+                        drop(sink);
+                        status = match &result {
+                            Ok(()) => 0,
+                            Err(err) => exit_code_for(err),
+                        };
+                        result?;
+                    } else {
+                        pending_builtin = Some((builtin, sink));
+                    }
+                }
+            }
+        }
+
+        let mut last_exit_status = None;
+        for mut child in children {
+            last_exit_status = Some(child.wait()?);
+        }
+        if last_stage_is_external {
+            status = last_exit_status.and_then(|s| s.code()).unwrap_or(1);
+        }
+
+        Ok(status)
+    }
+}
+
+/// Runs a builtin deferred by a non-final stage of [`Pipeline::run`], once
+/// the stage reading its output has been spawned and is draining the pipe.
+fn flush_pending_builtin(
+    pending: &mut Option<(Command, Box<dyn Write>)>,
+    shell: &mut Shell,
+    status: &mut i32,
+) -> Result<(), ShellError> {
+    if let Some((builtin, mut sink)) = pending.take() {
+        let result = builtin.run(shell, sink.as_mut());
+        drop(sink);
+        *status = match &result {
+            Ok(()) => 0,
+            Err(err) => exit_code_for(err),
+        };
+        result?;
+    }
+    Ok(())
+}
+
+fn resolve_stdout(redirect: &Redirect, is_last: bool) -> io::Result<Stdio> {
+    if let Some(target) = &redirect.stdout {
+        Ok(Stdio::from(target.open()?))
+    } else if is_last {
+        Ok(Stdio::inherit())
+    } else {
+        Ok(Stdio::piped())
+    }
+}
+
+fn resolve_stderr(redirect: &Redirect) -> io::Result<Stdio> {
+    if let Some(target) = &redirect.stderr {
+        Ok(Stdio::from(target.open()?))
+    } else {
+        Ok(Stdio::inherit())
+    }
+}
+
+/// Builtins only ever produce output, so piping one into a later stage just
+/// needs a pipe to hand the next stage's stdin, not a full child process.
+fn resolve_builtin_sink(
+    redirect: &Redirect,
+    is_last: bool,
+) -> io::Result<(Box<dyn Write>, Option<Stdio>)> {
+    if let Some(target) = &redirect.stdout {
+        Ok((Box::new(target.open()?), None))
+    } else if is_last {
+        Ok((Box::new(io::stdout()), None))
+    } else {
+        let (reader, writer) = io::pipe()?;
+        Ok((Box::new(writer), Some(Stdio::from(reader))))
+    }
+}
+
+/// Reads from `reader` until the accumulated input is no longer mid-quote or
+/// ending in an unquoted trailing backslash, joining continuation lines the
+/// way POSIX shells do. Returns `None` on EOF; an unfinished continuation
+/// pending at that point is discarded.
+fn read_logical_line(reader: &mut LineReader, history: &[String], prompt: &str) -> Option<String> {
+    let mut buf = String::new();
+    loop {
+        let line_prompt = if buf.is_empty() { prompt } else { "" };
+        let line = reader.read_line(line_prompt, history)?;
+        buf.push_str(&line);
+
+        match line_status(&buf) {
+            LineStatus::Complete => return Some(buf),
+            LineStatus::NeedsMore {
+                strip_trailing_backslash,
+            } => {
+                if strip_trailing_backslash {
+                    buf.pop();
+                } else {
+                    buf.push('\n');
+                }
+            }
+        }
+    }
+}
+
+/// Reads a single line of input, optionally with raw-mode editing (arrow-key
+/// cursor movement and history recall). Kept separate from `Shell::run` so
+/// the editing behavior can be exercised independently of command execution.
+struct LineReader;
+
+impl LineReader {
+    fn new() -> Self {
+        LineReader
+    }
+
+    fn read_line(&mut self, prompt: &str, history: &[String]) -> Option<String> {
+        if enable_raw_mode() {
+            let result = self.read_line_editing(prompt, history);
+            disable_raw_mode();
+            result
+        } else {
+            Self::read_line_plain(prompt)
+        }
+    }
+
+    /// Fallback used when stdin isn't a real terminal (pipes, redirected
+    /// input) since there is nothing to put into raw mode.
+    fn read_line_plain(prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            return None;
+        }
+        Some(line.trim_end_matches(['\n', '\r']).to_string())
+    }
+
+    fn read_line_editing(&mut self, prompt: &str, history: &[String]) -> Option<String> {
+        let mut buf: Vec<char> = Vec::new();
+        let mut cursor = 0usize;
+        let mut history_index = history.len();
+        let mut stash = String::new();
+        let mut stdin = io::stdin();
+        let mut byte = [0u8; 1];
+
+        print!("{}", prompt);
+        io::stdout().flush().ok();
+
+        loop {
+            if stdin.read_exact(&mut byte).is_err() {
+                print!("\r\n");
+                return None;
+            }
+            match byte[0] {
+                b'\r' | b'\n' => {
+                    print!("\r\n");
+                    io::stdout().flush().ok();
+                    return Some(buf.into_iter().collect());
+                }
+                0x04 if buf.is_empty() => {
+                    print!("\r\n");
+                    return None;
+                }
+                0x7f | 0x08 if cursor > 0 => {
+                    cursor -= 1;
+                    buf.remove(cursor);
+                    redraw_line(prompt, &buf, cursor);
+                }
+                0x1b => {
+                    let mut seq = [0u8; 2];
+                    if stdin.read_exact(&mut seq).is_err() || seq[0] != b'[' {
+                        continue;
+                    }
+                    match seq[1] {
+                        b'A' if history_index > 0 => {
+                            if history_index == history.len() {
+                                stash = buf.iter().collect();
+                            }
+                            history_index -= 1;
+                            buf = history[history_index].chars().collect();
+                            cursor = buf.len();
+                            redraw_line(prompt, &buf, cursor);
+                        }
+                        b'B' if history_index < history.len() => {
+                            history_index += 1;
+                            buf = if history_index == history.len() {
+                                stash.chars().collect()
+                            } else {
+                                history[history_index].chars().collect()
+                            };
+                            cursor = buf.len();
+                            redraw_line(prompt, &buf, cursor);
+                        }
+                        b'C' if cursor < buf.len() => {
+                            cursor += 1;
+                            redraw_line(prompt, &buf, cursor);
+                        }
+                        b'D' if cursor > 0 => {
+                            cursor -= 1;
+                            redraw_line(prompt, &buf, cursor);
+                        }
+                        _ => {}
+                    }
+                }
+                c if c.is_ascii_graphic() || c == b' ' => {
+                    buf.insert(cursor, c as char);
+                    cursor += 1;
+                    redraw_line(prompt, &buf, cursor);
+                }
+                _ => {}
+            }
         }
     }
 }
 
-fn parse_args(input: &str) -> Vec<String> {
-    let mut parts = Vec::new();
-    let mut current_str = String::new();
+fn redraw_line(prompt: &str, buf: &[char], cursor: usize) {
+    let line: String = buf.iter().collect();
+    print!("\r\x1b[K{}{}", prompt, line);
+    if cursor < buf.len() {
+        print!("\x1b[{}D", buf.len() - cursor);
+    }
+    io::stdout().flush().ok();
+}
+
+/// Raw mode is toggled by shelling out to `stty` rather than binding to a
+/// terminal-control crate, keeping this shell dependency-free. `stty` fails
+/// when stdin isn't a tty (pipes, test harnesses), which doubles as our
+/// signal to fall back to plain line reading.
+fn enable_raw_mode() -> bool {
+    process::Command::new("stty")
+        .args(["raw", "-echo"])
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn disable_raw_mode() {
+    let _ = process::Command::new("stty")
+        .arg("sane")
+        .stderr(Stdio::null())
+        .status();
+}
+
+enum LineStatus {
+    Complete,
+    NeedsMore { strip_trailing_backslash: bool },
+}
 
+/// Scans for unterminated quotes or a trailing unescaped backslash, the two
+/// conditions under which a shell reads another line before parsing.
+fn line_status(input: &str) -> LineStatus {
+    #[derive(Copy, Clone, PartialEq)]
+    enum State {
+        Normal,
+        SingleQuotes,
+        DoubleQuotes,
+    }
+    let mut state = State::Normal;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (state, c) {
+            (State::Normal, '\\') if chars.next().is_none() => {
+                return LineStatus::NeedsMore {
+                    strip_trailing_backslash: true,
+                };
+            }
+            (State::Normal, '\'') => state = State::SingleQuotes,
+            (State::Normal, '\"') => state = State::DoubleQuotes,
+            (State::SingleQuotes, '\'') | (State::DoubleQuotes, '\"') => state = State::Normal,
+            (State::DoubleQuotes, '\\') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    if state == State::Normal {
+        LineStatus::Complete
+    } else {
+        LineStatus::NeedsMore {
+            strip_trailing_backslash: false,
+        }
+    }
+}
+
+/// Resolves a `$NAME`/`${NAME}`/`$?` reference starting right after the `$`
+/// that `tokenize` already consumed. Returns `None` when the following
+/// character doesn't start a valid parameter name, in which case the `$` is
+/// kept literally.
+fn expand_variable(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    shell: &Shell,
+) -> Option<String> {
+    match chars.peek() {
+        Some('?') => {
+            chars.next();
+            Some(shell.last_status.to_string())
+        }
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                name.push(c);
+            }
+            Some(env::var(&name).unwrap_or_default())
+        }
+        Some(&c) if c.is_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    name.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            Some(env::var(&name).unwrap_or_default())
+        }
+        _ => None,
+    }
+}
+
+fn tokenize(input: &str, shell: &Shell) -> Vec<Token> {
     #[derive(Copy, Clone)]
     enum State {
         Normal,
@@ -140,10 +777,19 @@ fn parse_args(input: &str) -> Vec<String> {
         DoubleQuotes,
     }
     let mut state = State::Normal;
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.chars().peekable();
 
-    for c in input.chars() {
-        // Matching like this needs to clone state, but that's trivial since
-        // it has no payload (basically just cloning an integer so very cheap)
+    macro_rules! flush_word {
+        () => {
+            if !current.is_empty() {
+                tokens.push(Token::Word(std::mem::take(&mut current)));
+            }
+        };
+    }
+
+    while let Some(c) = chars.next() {
         match (state, c) {
             (State::Normal, '\'') => {
                 state = State::SingleQuotes;
@@ -154,27 +800,137 @@ fn parse_args(input: &str) -> Vec<String> {
             (State::SingleQuotes, '\'') | (State::DoubleQuotes, '\"') => {
                 state = State::Normal;
             }
-            (State::Normal, c) if c.is_whitespace() => {
-                if !current_str.is_empty() {
-                    // no clone here, and take resets the string back to Default
-                    parts.push(std::mem::take(&mut current_str));
+            (State::Normal, '\\') => match chars.next() {
+                // An unquoted trailing backslash is a line continuation that
+                // `read_logical_line` already resolved before we get here.
+                Some('\n') | None => {}
+                Some(next) => current.push(next),
+            },
+            (State::DoubleQuotes, '\\') => match chars.peek() {
+                Some('"') | Some('\\') | Some('$') => current.push(chars.next().unwrap()),
+                Some('\n') => {
+                    chars.next();
+                }
+                _ => current.push('\\'),
+            },
+            (State::Normal, '$') | (State::DoubleQuotes, '$') => {
+                match expand_variable(&mut chars, shell) {
+                    Some(value) => current.push_str(&value),
+                    None => current.push('$'),
                 }
             }
-            (_, c) => {
-                current_str.push(c);
+            (State::Normal, c) if c.is_whitespace() => flush_word!(),
+            (State::Normal, '|') => {
+                flush_word!();
+                tokens.push(Token::Pipe);
             }
+            (State::Normal, '>') => {
+                flush_word!();
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token::Redirect(RedirectOp::StdoutAppend));
+                } else {
+                    tokens.push(Token::Redirect(RedirectOp::StdoutTrunc));
+                }
+            }
+            (State::Normal, '<') => {
+                flush_word!();
+                tokens.push(Token::Redirect(RedirectOp::StdinRead));
+            }
+            (State::Normal, '2') if current.is_empty() && chars.peek() == Some(&'>') => {
+                chars.next();
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                    tokens.push(Token::Redirect(RedirectOp::StderrAppend));
+                } else {
+                    tokens.push(Token::Redirect(RedirectOp::StderrTrunc));
+                }
+            }
+            (_, c) => current.push(c),
         }
     }
-    if !current_str.is_empty() {
-        parts.push(current_str);
+    flush_word!();
+    tokens
+}
+
+fn split_on_pipes(tokens: Vec<Token>) -> Vec<Vec<Token>> {
+    let mut stages = Vec::new();
+    let mut current = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Pipe => stages.push(std::mem::take(&mut current)),
+            other => current.push(other),
+        }
     }
-    parts
+    stages.push(current);
+    stages
 }
 
-struct ExitCmd;
+fn parse_stage(tokens: Vec<Token>) -> Result<(Vec<String>, Redirect), ShellError> {
+    let mut words = Vec::new();
+    let mut redirect = Redirect::default();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Pipe => unreachable!("pipes are split out before stage parsing"),
+            Token::Redirect(op) => {
+                let Some(Token::Word(path)) = tokens.next() else {
+                    return Err(ShellError::ShellMessage(
+                        "syntax error: expected a word after redirection operator".to_string(),
+                    ));
+                };
+                let path = PathBuf::from(path);
+                match op {
+                    RedirectOp::StdoutTrunc => {
+                        redirect.stdout = Some(RedirectTarget {
+                            path,
+                            append: false,
+                        })
+                    }
+                    RedirectOp::StdoutAppend => {
+                        redirect.stdout = Some(RedirectTarget { path, append: true })
+                    }
+                    RedirectOp::StderrTrunc => {
+                        redirect.stderr = Some(RedirectTarget {
+                            path,
+                            append: false,
+                        })
+                    }
+                    RedirectOp::StderrAppend => {
+                        redirect.stderr = Some(RedirectTarget { path, append: true })
+                    }
+                    RedirectOp::StdinRead => redirect.stdin = Some(path),
+                }
+            }
+        }
+    }
+
+    Ok((words, redirect))
+}
+
+struct ExitCmd {
+    args: Vec<String>,
+}
 impl CommandExec for ExitCmd {
-    fn run(self, _shell: &mut Shell) -> Result<(), ShellError> {
-        process::exit(0)
+    fn run(self, shell: &mut Shell, _out: &mut dyn Write) -> Result<(), ShellError> {
+        let code = match self.args.first() {
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(code) => code,
+                Err(_) => {
+                    eprintln!("exit: {}: numeric argument required", arg);
+                    shell.save_history();
+                    process::exit(2);
+                }
+            },
+            None => shell.last_status as i64,
+        };
+        // Shells report exit codes mod 256; rem_euclid keeps negative codes
+        // (e.g. `exit -1`) mapped to the same positive byte a real exit() would use.
+        // process::exit skips destructors, so history must be flushed explicitly.
+        shell.save_history();
+        process::exit(code.rem_euclid(256) as i32)
     }
 }
 
@@ -182,8 +938,8 @@ struct EchoCmd {
     args: String,
 }
 impl CommandExec for EchoCmd {
-    fn run(self, _shell: &mut Shell) -> Result<(), ShellError> {
-        println!("{}", self.args);
+    fn run(self, _shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
+        writeln!(out, "{}", self.args)?;
         Ok(())
     }
 }
@@ -192,23 +948,26 @@ struct TypeCmd {
     arg: String,
 }
 impl CommandExec for TypeCmd {
-    fn run(self, shell: &mut Shell) -> Result<(), ShellError> {
+    fn run(self, shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
         if shell.builtins.iter().any(|builtin| builtin == &self.arg) {
-            println!("{} is a shell builtin", self.arg);
-            return Ok(());
-        } else if let Some(executable_path) = shell.try_find_executable(&self.arg) {
-            println!("{} is {}", self.arg, executable_path.display());
+            writeln!(out, "{} is a shell builtin", self.arg)?;
             return Ok(());
         }
-        return Err(ShellError::NotFound(self.arg.into()));
+        match shell.try_find_executable(&self.arg) {
+            Lookup::Found(path) | Lookup::NotExecutable(path) => {
+                writeln!(out, "{} is {}", self.arg, path.display())?;
+                Ok(())
+            }
+            Lookup::NotFound => Err(ShellError::NotFound(self.arg)),
+        }
     }
 }
 
 struct PwdCmd;
 impl CommandExec for PwdCmd {
-    fn run(self, _shell: &mut Shell) -> Result<(), ShellError> {
+    fn run(self, _shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
         let cwd = std::env::current_dir()?;
-        println!("{}", cwd.display());
+        writeln!(out, "{}", cwd.display())?;
         Ok(())
     }
 }
@@ -217,7 +976,7 @@ struct CdCmd {
     path: String,
 }
 impl CommandExec for CdCmd {
-    fn run(self, _shell: &mut Shell) -> Result<(), ShellError> {
+    fn run(self, _shell: &mut Shell, _out: &mut dyn Write) -> Result<(), ShellError> {
         let actual_path: PathBuf = if let Some(stripped_path) = self.path.strip_prefix("~") {
             let home_path = std::env::var("HOME").expect("home should not be empty");
             PathBuf::from(home_path).join(stripped_path)
@@ -234,25 +993,82 @@ impl CommandExec for CdCmd {
     }
 }
 
-struct ExternalCmd<'a> {
-    name: &'a str,
+struct ExportCmd {
     args: Vec<String>,
 }
-impl CommandExec for ExternalCmd<'_> {
-    fn run(self, shell: &mut Shell) -> Result<(), ShellError> {
-        match shell.try_find_executable(self.name) {
-            Some(_) => {
-                let output = std::process::Command::new(self.name)
-                    .args(self.args)
-                    .output()?;
-                io::stdout().write_all(&output.stdout)?;
-                Ok(())
+impl CommandExec for ExportCmd {
+    fn run(self, _shell: &mut Shell, _out: &mut dyn Write) -> Result<(), ShellError> {
+        for arg in &self.args {
+            if let Some((name, value)) = arg.split_once('=') {
+                // SAFETY: the shell is single-threaded, so there's no other
+                // thread that could observe the environment mid-mutation.
+                unsafe { env::set_var(name, value) };
+            }
+        }
+        Ok(())
+    }
+}
+
+struct EnvCmd {
+    args: Vec<String>,
+}
+impl CommandExec for EnvCmd {
+    fn run(self, _shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
+        if self.args.is_empty() {
+            for (name, value) in env::vars() {
+                writeln!(out, "{}={}", name, value)?;
+            }
+        } else {
+            for name in &self.args {
+                if let Ok(value) = env::var(name) {
+                    writeln!(out, "{}", value)?;
+                }
             }
-            None => return Err(ShellError::NotFound(self.name.into())),
         }
+        Ok(())
+    }
+}
+
+struct HistoryCmd;
+impl CommandExec for HistoryCmd {
+    fn run(self, shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
+        for (index, entry) in shell.history.iter().enumerate() {
+            writeln!(out, "{:5}  {}", index + 1, entry)?;
+        }
+        Ok(())
     }
 }
 
+struct WhichCmd {
+    args: Vec<String>,
+}
+impl CommandExec for WhichCmd {
+    fn run(self, shell: &mut Shell, out: &mut dyn Write) -> Result<(), ShellError> {
+        let mut first_missing = None;
+        for name in &self.args {
+            match shell.try_find_executable(name) {
+                Lookup::Found(path) | Lookup::NotExecutable(path) => {
+                    writeln!(out, "{}", path.display())?;
+                }
+                Lookup::NotFound => {
+                    if first_missing.is_none() {
+                        first_missing = Some(name.clone());
+                    }
+                }
+            }
+        }
+        match first_missing {
+            Some(name) => Err(ShellError::NotFound(name)),
+            None => Ok(()),
+        }
+    }
+}
+
+struct ExternalCmd {
+    name: String,
+    args: Vec<String>,
+}
+
 #[cfg(unix)]
 fn is_executable(metadata: &fs::Metadata) -> bool {
     use std::os::unix::fs::PermissionsExt;